@@ -1,10 +1,57 @@
 //! Programmable IO (PIO)
 /// See [Chapter 3](https://rptl.io/pico-datasheet) for more details.
 
+use core::sync::atomic::{compiler_fence, AtomicU8, Ordering};
+use core::task::{Context, Poll, Waker};
+
+use crate::gpio::{Pin, PinId, PinMode};
+
 const PIO_INSTRUCTION_COUNT: usize = 32;
 
 /// PIO Instance
-pub trait Instance: core::ops::Deref<Target = rp2040_pac::pio0::RegisterBlock> {}
+pub trait Instance: core::ops::Deref<Target = rp2040_pac::pio0::RegisterBlock> {
+    /// Index of this PIO block (0 or 1).
+    ///
+    /// Used to compute the DREQ number a DMA channel must be paced against when it is
+    /// transferring to or from one of this block's state machines.
+    fn id() -> u8;
+
+    /// The GPIO alternate function that routes a pad to this PIO block.
+    type PinFunction: PinMode;
+}
+
+/// Number of DREQ numbers claimed by each PIO block: four TX DREQs followed by four RX DREQs,
+/// one pair per state machine.
+const DREQS_PER_PIO: u8 = 8;
+const TX_DREQS_PER_PIO: u8 = 4;
+
+/// Compute the `TREQ_SEL` value that paces a DMA channel against one of a PIO block's state
+/// machine FIFOs. See the RP2040 datasheet's DREQ table.
+fn dreq_for_sm<P: Instance>(sm_id: u8, rx: bool) -> u8 {
+    P::id() * DREQS_PER_PIO + if rx { TX_DREQS_PER_PIO + sm_id } else { sm_id }
+}
+
+/// Word size used for the read and write sides of a DMA transfer to/from a state machine FIFO.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DmaWordSize {
+    /// 8 bits.
+    Byte,
+    /// 16 bits.
+    HalfWord,
+    /// 32 bits, the FIFO's native width.
+    Word,
+}
+
+impl DmaWordSize {
+    /// `DATA_SIZE` encoding used by `CH_CTRL_TRIG`/`CH_AL1_CTRL`.
+    fn data_size_bits(self) -> u8 {
+        match self {
+            DmaWordSize::Byte => 0,
+            DmaWordSize::HalfWord => 1,
+            DmaWordSize::Word => 2,
+        }
+    }
+}
 
 /// Programmable IO Block
 pub struct PIO<P: Instance> {
@@ -31,21 +78,25 @@ impl<P: Instance> PIO<P> {
                 StateMachine {
                     id: 0,
                     block: pio.deref(),
+                    pins: core::cell::RefCell::new(Default::default()),
                     _phantom: core::marker::PhantomData,
                 },
                 StateMachine {
                     id: 1,
                     block: pio.deref(),
+                    pins: core::cell::RefCell::new(Default::default()),
                     _phantom: core::marker::PhantomData,
                 },
                 StateMachine {
                     id: 2,
                     block: pio.deref(),
+                    pins: core::cell::RefCell::new(Default::default()),
                     _phantom: core::marker::PhantomData,
                 },
                 StateMachine {
                     id: 3,
                     block: pio.deref(),
+                    pins: core::cell::RefCell::new(Default::default()),
                     _phantom: core::marker::PhantomData,
                 },
             ],
@@ -77,28 +128,161 @@ impl<P: Instance> PIO<P> {
                     Some(origin as usize)
                 }
             } else {
-                for i in (32 - i.len())..=0 {
-                    if self.used_instruction_space.get() & (mask << i) == 0 {
-                        return Some(i);
-                    }
-                }
-                None
+                // Search from the highest possible offset down to zero, so that programs
+                // loaded without a fixed origin tend to pack toward the top of instruction
+                // memory, leaving low offsets free for programs that need one (e.g. origin 0).
+                (0..=PIO_INSTRUCTION_COUNT - i.len())
+                    .rev()
+                    .find(|&offset| self.used_instruction_space.get() & (mask << offset) == 0)
             }
         }
     }
 
-    fn add_program(&self, instructions: &[u16], origin: Option<u8>) -> Option<usize> {
-        if let Some(offset) = self.find_offset_for_instructions(instructions, origin) {
-            for (i, instr) in instructions.iter().enumerate() {
-                self.pio.instr_mem[i + offset].write(|w| unsafe { w.bits(*instr as u32) })
-            }
-            self.used_instruction_space
-                .set(self.used_instruction_space.get() | ((1 << instructions.len()) - 1));
-            Some(offset)
-        } else {
-            None
+    /// Relocate and install `program` into this PIO block's instruction memory, to wherever
+    /// there's free space (or to the fixed offset it declares via `.origin`, if any).
+    ///
+    /// Returns `None` if there isn't enough free space (or, with a fixed origin, if those
+    /// particular slots are already taken).
+    pub fn install<'a, T>(&self, program: &'a pio::Program<T>) -> Option<InstalledProgram<P>> {
+        let offset = self.find_offset_for_instructions(program.code(), program.origin())? as u8;
+        self.add_program(&RelocatedProgram::new(program, offset))
+    }
+
+    /// Load an already-[relocated](RelocatedProgram) program into this PIO block's instruction
+    /// memory, at the offset it was relocated to.
+    ///
+    /// Returns `None` if those instruction slots aren't actually free.
+    pub fn add_program(&self, program: &RelocatedProgram<'_>) -> Option<InstalledProgram<P>> {
+        let offset = program.offset() as usize;
+        let len = program.instruction_count();
+        if len > PIO_INSTRUCTION_COUNT || offset > PIO_INSTRUCTION_COUNT - len {
+            return None;
+        }
+        let used_mask = (((1u64 << len) - 1) << offset) as u32;
+        if self.used_instruction_space.get() & used_mask != 0 {
+            return None;
+        }
+        for (i, instr) in program.code().enumerate() {
+            self.pio.instr_mem[i + offset].write(|w| unsafe { w.bits(instr as u32) })
+        }
+        self.used_instruction_space
+            .set(self.used_instruction_space.get() | used_mask);
+        Some(InstalledProgram {
+            offset: offset as u8,
+            used_mask,
+            _phantom: core::marker::PhantomData,
+        })
+    }
+
+    /// Free the instruction memory occupied by `program` so it can be reused by a later
+    /// [`PIO::add_program`] call.
+    ///
+    /// # Safety
+    ///
+    /// The caller must guarantee that no state machine is still executing, or will be
+    /// (re-)started into, `program`'s instruction range. This can't be checked statically, since
+    /// a state machine's program counter is independent of what's currently loaded there.
+    pub unsafe fn uninstall(&self, program: InstalledProgram<P>) {
+        self.used_instruction_space
+            .set(self.used_instruction_space.get() & !program.used_mask);
+    }
+}
+
+/// A program that has been loaded into a PIO block's instruction memory.
+///
+/// Obtained from [`PIO::add_program`]. Pass it to [`PIO::uninstall`] once nothing is executing
+/// out of it any more to reclaim its instruction slots. Dropping it without uninstalling simply
+/// leaks that space for the lifetime of the `PIO`.
+#[derive(Debug)]
+pub struct InstalledProgram<P: Instance> {
+    offset: u8,
+    used_mask: u32,
+    _phantom: core::marker::PhantomData<P>,
+}
+
+impl<P: Instance> InstalledProgram<P> {
+    /// The instruction memory offset this program was loaded at.
+    pub fn offset(&self) -> u8 {
+        self.offset
+    }
+}
+
+/// A program's instructions, relocated to load at a particular instruction memory offset.
+///
+/// Programs are assembled assuming they'll be loaded at offset 0, so any `jmp` targets in their
+/// code are addresses relative to that origin. Loading the same words anywhere else silently
+/// breaks those jumps unless the addresses are shifted by the same amount first. This does that
+/// shift lazily, instruction by instruction, via [`RelocatedProgram::code`], so programs with
+/// internal jumps (i.e. loops) load correctly no matter which free offset they end up at.
+#[derive(Debug)]
+pub struct RelocatedProgram<'a> {
+    code: &'a [u16],
+    offset: u8,
+    wrap: (u8, u8),
+    origin: Option<u8>,
+}
+
+impl<'a> RelocatedProgram<'a> {
+    /// Relocate `program`'s instructions to load at instruction memory offset `offset`.
+    pub fn new<T>(program: &'a pio::Program<T>, offset: u8) -> Self {
+        let (wrap_top, wrap_bottom) = program.wrap();
+        RelocatedProgram::from_parts(
+            program.code(),
+            (wrap_top, wrap_bottom),
+            program.origin(),
+            offset,
+        )
+    }
+
+    fn from_parts(code: &'a [u16], wrap: (u8, u8), origin: Option<u8>, offset: u8) -> Self {
+        RelocatedProgram {
+            code,
+            offset,
+            wrap: (
+                wrap.0.wrapping_add(offset) & 0x1f,
+                wrap.1.wrapping_add(offset) & 0x1f,
+            ),
+            origin,
         }
     }
+
+    /// Iterate over this program's instructions, with `jmp` targets shifted to match `offset`.
+    ///
+    /// A `jmp`'s top three bits are `000`, the only opcode whose operand is an absolute
+    /// instruction-memory address; every other instruction is returned unchanged.
+    pub fn code(&self) -> impl Iterator<Item = u16> + '_ {
+        const OPCODE_MASK: u16 = 0b111 << 13;
+        const JMP_OPCODE: u16 = 0b000 << 13;
+        let offset = self.offset;
+        self.code.iter().map(move |&word| {
+            if word & OPCODE_MASK == JMP_OPCODE {
+                let addr = (word & 0x1f) as u8;
+                (word & !0x1f) | (addr.wrapping_add(offset) & 0x1f) as u16
+            } else {
+                word
+            }
+        })
+    }
+
+    /// Number of instruction words in this program.
+    pub fn instruction_count(&self) -> usize {
+        self.code.len()
+    }
+
+    /// The relocated `(wrap_top, wrap_bottom)` pair.
+    pub fn wrap(&self) -> (u8, u8) {
+        self.wrap
+    }
+
+    /// The fixed offset this program declares via its `.origin` directive, if any.
+    pub fn origin(&self) -> Option<u8> {
+        self.origin
+    }
+
+    /// The instruction memory offset this program was relocated to.
+    pub fn offset(&self) -> u8 {
+        self.offset
+    }
 }
 
 /// PIO State Machine.
@@ -106,6 +290,10 @@ impl<P: Instance> PIO<P> {
 pub struct StateMachine<P: Instance> {
     id: u8,
     block: *const rp2040_pac::pio0::RegisterBlock,
+    // Keeps the pins configured by the last `PIOBuilder::build` call alive (in the order
+    // in_base, out_base, set_base, sideset_base, jmp_pin), so their pads get reverted to
+    // `FunctionNull` once this state machine (and thus the owning `PIO`) is dropped.
+    pins: core::cell::RefCell<[Option<PioPin<P>>; 5]>,
     _phantom: core::marker::PhantomData<P>,
 }
 
@@ -182,12 +370,420 @@ impl<P: Instance> StateMachine<P> {
     fn sm(&self) -> &rp2040_pac::pio0::SM {
         &self.block().sm[self.id as usize]
     }
+
+    /// The DREQ number a DMA channel must be paced against to feed this state machine's TX FIFO.
+    fn tx_dreq(&self) -> u8 {
+        dreq_for_sm::<P>(self.id, false)
+    }
+
+    /// The DREQ number a DMA channel must be paced against to drain this state machine's RX FIFO.
+    fn rx_dreq(&self) -> u8 {
+        dreq_for_sm::<P>(self.id, true)
+    }
+
+    /// Start a DMA transfer from `buffer` into this state machine's TX FIFO, paced by the state
+    /// machine's TX DREQ, and return immediately without waiting for it to complete.
+    ///
+    /// # Safety
+    ///
+    /// `ch` must be an idle DMA channel, and `buffer` must remain valid and untouched by the CPU
+    /// until the transfer has finished (e.g. by polling `ch`'s busy flag). `buffer.len()` must be
+    /// a multiple of `word_size`'s byte width, or the transfer count is truncated and the
+    /// trailing partial word is silently dropped.
+    pub unsafe fn tx_dma_transfer(
+        &self,
+        ch: &rp2040_pac::dma::CH,
+        buffer: &[u8],
+        word_size: DmaWordSize,
+    ) {
+        debug_assert_eq!(
+            buffer.len() % (1 << word_size.data_size_bits()),
+            0,
+            "buffer length must be a multiple of word_size's byte width"
+        );
+
+        let txf = &self.block().txf[self.id as usize] as *const _ as u32;
+
+        unsafe {
+            ch.ch_read_addr.write(|w| w.bits(buffer.as_ptr() as u32));
+            ch.ch_write_addr.write(|w| w.bits(txf));
+            ch.ch_trans_count
+                .write(|w| w.bits(buffer.len() as u32 >> word_size.data_size_bits()));
+        }
+
+        // Ensure buffer writes are visible to the DMA engine before it is kicked off, and that
+        // the compiler doesn't hoist buffer accesses across the handoff.
+        compiler_fence(Ordering::SeqCst);
+
+        ch.ch_ctrl_trig.write(|w| {
+            unsafe {
+                w.treq_sel().bits(self.tx_dreq());
+                w.data_size().bits(word_size.data_size_bits());
+            }
+            w.incr_read().bit(true);
+            w.incr_write().bit(false);
+            w.en().bit(true)
+        });
+
+        compiler_fence(Ordering::SeqCst);
+    }
+
+    /// Start a DMA transfer from this state machine's RX FIFO into `buffer`, paced by the state
+    /// machine's RX DREQ, and return immediately without waiting for it to complete.
+    ///
+    /// # Safety
+    ///
+    /// `ch` must be an idle DMA channel, and `buffer` must remain valid and untouched by the CPU
+    /// until the transfer has finished (e.g. by polling `ch`'s busy flag). `buffer.len()` must be
+    /// a multiple of `word_size`'s byte width, or the transfer count is truncated and the
+    /// trailing partial word is silently dropped.
+    pub unsafe fn rx_dma_transfer(
+        &self,
+        ch: &rp2040_pac::dma::CH,
+        buffer: &mut [u8],
+        word_size: DmaWordSize,
+    ) {
+        debug_assert_eq!(
+            buffer.len() % (1 << word_size.data_size_bits()),
+            0,
+            "buffer length must be a multiple of word_size's byte width"
+        );
+
+        let rxf = &self.block().rxf[self.id as usize] as *const _ as u32;
+
+        unsafe {
+            ch.ch_read_addr.write(|w| w.bits(rxf));
+            ch.ch_write_addr
+                .write(|w| w.bits(buffer.as_mut_ptr() as u32));
+            ch.ch_trans_count
+                .write(|w| w.bits(buffer.len() as u32 >> word_size.data_size_bits()));
+        }
+
+        compiler_fence(Ordering::SeqCst);
+
+        ch.ch_ctrl_trig.write(|w| {
+            unsafe {
+                w.treq_sel().bits(self.rx_dreq());
+                w.data_size().bits(word_size.data_size_bits());
+            }
+            w.incr_read().bit(false);
+            w.incr_write().bit(true);
+            w.en().bit(true)
+        });
+
+        compiler_fence(Ordering::SeqCst);
+    }
+}
+
+/// Bit index within a PIO block's `INTR`/`IRQ0_INTE`/`IRQ0_INTS` registers.
+///
+/// Each PIO block packs 12 flags into these registers: four RX-not-empty flags (one per state
+/// machine), four TX-not-full flags, and four flags set by a state machine's `irq` instruction.
+/// Indexing the waker table directly by this bit number (rather than keeping three separate
+/// per-kind arrays) keeps the shared interrupt handler a single straight-line loop.
+fn irq_bit_index(sm_id: u8, kind: IrqKind) -> usize {
+    match kind {
+        IrqKind::RxNotEmpty => sm_id as usize,
+        IrqKind::TxNotFull => 4 + sm_id as usize,
+        IrqKind::Irq => 8 + sm_id as usize,
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum IrqKind {
+    RxNotEmpty,
+    TxNotFull,
+    Irq,
+}
+
+/// A single waiting waker, registered from `poll` and woken from the shared interrupt handler.
+///
+/// Unlike the FIFO/IRQ flags themselves, registering and swapping out a waker is not a single
+/// atomic operation, so this does take a brief critical section.
+struct AtomicWaker {
+    waker: critical_section::Mutex<core::cell::RefCell<Option<Waker>>>,
+}
+
+impl AtomicWaker {
+    const fn new() -> Self {
+        Self {
+            waker: critical_section::Mutex::new(core::cell::RefCell::new(None)),
+        }
+    }
+
+    fn register(&self, waker: &Waker) {
+        critical_section::with(|cs| {
+            self.waker.borrow(cs).replace(Some(waker.clone()));
+        });
+    }
+
+    fn wake(&self) {
+        critical_section::with(|cs| {
+            if let Some(waker) = self.waker.borrow(cs).borrow_mut().take() {
+                waker.wake();
+            }
+        });
+    }
+}
+
+/// One waker per IRQ register bit, per PIO block, indexed `[P::id()][bit]` so PIO0 and PIO1
+/// futures never alias the same slot.
+static PIO_WAKERS: [[AtomicWaker; 12]; 2] = [[AtomicWaker::new(); 12]; 2];
+
+impl<P: Instance> PIO<P> {
+    /// Shared interrupt handler for this PIO block's `IRQ0` line.
+    ///
+    /// Call this (and only this) from the block's `PIOx_IRQ_0` vector. It reads the masked
+    /// `IRQ0_INTS` status once, wakes every future that was waiting on a now-ready bit, and
+    /// disables that bit's `IRQ0_INTE` so the line doesn't keep firing before the future gets a
+    /// chance to re-poll and re-arm it. Handling all 12 bits here keeps `IRQ1` free for user code.
+    pub fn irq0(&self) {
+        self.handle_irq(0);
+    }
+
+    fn handle_irq(&self, irq_index: u8) {
+        let ints = if irq_index == 0 {
+            self.pio.ints0.read().bits()
+        } else {
+            self.pio.ints1.read().bits()
+        };
+
+        for bit in 0..12u8 {
+            if ints & (1 << bit) != 0 {
+                PIO_WAKERS[P::id() as usize][bit as usize].wake();
+                // `inte0`/`inte1` are read-modify-written here and in `enable_irq0_bit`, which
+                // runs from task context, so both sides need to be mutually exclusive.
+                critical_section::with(|_| {
+                    if irq_index == 0 {
+                        self.pio
+                            .inte0
+                            .modify(|r, w| unsafe { w.bits(r.bits() & !(1 << bit)) });
+                    } else {
+                        self.pio
+                            .inte1
+                            .modify(|r, w| unsafe { w.bits(r.bits() & !(1 << bit)) });
+                    }
+                });
+            }
+        }
+    }
+}
+
+impl<P: Instance> StateMachine<P> {
+    fn enable_irq0_bit(&self, bit: usize) {
+        // Matches the critical section around `handle_irq`'s `inte0`/`inte1` clearing, since
+        // that runs from the interrupt vector and could otherwise race this read-modify-write.
+        critical_section::with(|_| {
+            self.block()
+                .inte0
+                .modify(|r, w| unsafe { w.bits(r.bits() | (1 << bit)) });
+        });
+    }
+
+    /// Wait until the TX FIFO is not full, then push `word` into it.
+    pub fn wait_push(&self, word: u32) -> WaitPush<'_, P> {
+        WaitPush { sm: self, word }
+    }
+
+    /// Wait until the RX FIFO is not empty, then pull and return a word from it.
+    pub fn wait_pull(&self) -> WaitPull<'_, P> {
+        WaitPull { sm: self }
+    }
+
+    /// Wait until IRQ flag `n` of this state machine's PIO block is set (by any of the block's
+    /// state machines executing `irq set <n>` or `irq wait <n>`), then clear the flag.
+    ///
+    /// `n` is the IRQ flag number, 0 to 3.
+    pub fn wait_irq(&self, n: u8) -> WaitIrq<'_, P> {
+        assert!(n < 4, "PIO IRQ flag number must be 0..=3");
+        WaitIrq { sm: self, n }
+    }
+}
+
+/// Future returned by [`StateMachine::wait_push`].
+pub struct WaitPush<'a, P: Instance> {
+    sm: &'a StateMachine<P>,
+    word: u32,
+}
+
+impl<'a, P: Instance> core::future::Future for WaitPush<'a, P> {
+    type Output = ();
+
+    fn poll(self: core::pin::Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+        let bit = irq_bit_index(self.sm.id, IrqKind::TxNotFull);
+        if self.sm.block().intr.read().bits() & (1 << bit) != 0 {
+            self.sm.push(self.word);
+            Poll::Ready(())
+        } else {
+            PIO_WAKERS[P::id() as usize][bit].register(cx.waker());
+            self.sm.enable_irq0_bit(bit);
+            Poll::Pending
+        }
+    }
+}
+
+/// Future returned by [`StateMachine::wait_pull`].
+pub struct WaitPull<'a, P: Instance> {
+    sm: &'a StateMachine<P>,
+}
+
+impl<'a, P: Instance> core::future::Future for WaitPull<'a, P> {
+    type Output = u32;
+
+    fn poll(self: core::pin::Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<u32> {
+        let bit = irq_bit_index(self.sm.id, IrqKind::RxNotEmpty);
+        if self.sm.block().intr.read().bits() & (1 << bit) != 0 {
+            Poll::Ready(self.sm.pull())
+        } else {
+            PIO_WAKERS[P::id() as usize][bit].register(cx.waker());
+            self.sm.enable_irq0_bit(bit);
+            Poll::Pending
+        }
+    }
+}
+
+/// Future returned by [`StateMachine::wait_irq`].
+pub struct WaitIrq<'a, P: Instance> {
+    sm: &'a StateMachine<P>,
+    n: u8,
+}
+
+impl<'a, P: Instance> core::future::Future for WaitIrq<'a, P> {
+    type Output = ();
+
+    fn poll(self: core::pin::Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+        let bit = irq_bit_index(self.n, IrqKind::Irq);
+        if self.sm.block().intr.read().bits() & (1 << bit) != 0 {
+            // Raw IRQ flags are sticky (set by the `irq` instruction) and individually
+            // write-1-to-clear, so no critical section is needed here.
+            self.sm
+                .block()
+                .irq
+                .write(|w| unsafe { w.bits(1 << self.n) });
+            Poll::Ready(())
+        } else {
+            PIO_WAKERS[P::id() as usize][bit].register(cx.waker());
+            self.sm.enable_irq0_bit(bit);
+            Poll::Pending
+        }
+    }
+}
+
+/// Number of GPIO pads on RP2040.
+const GPIO_COUNT: usize = 30;
+
+/// `FUNCSEL` encoding for "no peripheral connected", used to release a pad back to the GPIO HAL.
+const FUNCSEL_NULL: u8 = 0x1f;
+
+/// Per-block, per-pin count of how many live [`PioPin`]s of each PIO block currently claim a
+/// given GPIO. Indexed `[pio_id][gpio_num]`.
+static PIN_REFCOUNTS: [[AtomicU8; GPIO_COUNT]; 2] = [[AtomicU8::new(0); GPIO_COUNT]; 2];
+
+/// A GPIO pin owned by a PIO block, with its pad switched to that block's alternate function.
+///
+/// Wrapping a [`Pin`] in a `PioPin` (via [`PioPin::new`]) hands ownership to the PIO
+/// machinery and routes the pad accordingly; passing it to a [`PIOBuilder`] setter then transfers
+/// ownership again, into the target [`StateMachine`]. Once the last `PioPin` referencing a given
+/// GPIO on this block is dropped, the pad is reverted to `FunctionNull` so other HAL drivers can
+/// reclaim it.
+pub struct PioPin<P: Instance> {
+    id: u8,
+    _phantom: core::marker::PhantomData<P>,
+}
+
+impl<P: Instance> core::fmt::Debug for PioPin<P> {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        f.debug_struct("PioPin").field("id", &self.id).finish()
+    }
+}
+
+impl<P: Instance> PioPin<P> {
+    /// Take ownership of `pin`, switching its pad to this PIO block's alternate function.
+    pub fn new<I: PinId, M: PinMode>(pin: Pin<I, M>) -> Self {
+        let id = pin.id().num;
+        PIN_REFCOUNTS[P::id() as usize][id as usize].fetch_add(1, Ordering::SeqCst);
+        let _ = pin.into_mode::<P::PinFunction>();
+        PioPin {
+            id,
+            _phantom: core::marker::PhantomData,
+        }
+    }
+
+    /// The GPIO number this pin wraps.
+    pub fn id(&self) -> u8 {
+        self.id
+    }
+
+    /// Set the pad's output drive strength.
+    pub fn set_drive_strength(&mut self, strength: DriveStrength) {
+        self.with_pad(|w| unsafe { w.drive().bits(strength.bits()) });
+    }
+
+    /// Configure the pad's pull-up/pull-down resistors.
+    pub fn set_pull(&mut self, pull_up: bool, pull_down: bool) {
+        self.with_pad(|w| w.pue().bit(pull_up).pde().bit(pull_down));
+    }
+
+    /// Enable or disable the pad's fast slew rate.
+    pub fn set_slew_fast(&mut self, fast: bool) {
+        self.with_pad(|w| w.slewfast().bit(fast));
+    }
+
+    fn with_pad(
+        &self,
+        f: impl FnOnce(
+            &mut rp2040_pac::pads_bank0::gpio::W,
+        ) -> &mut rp2040_pac::pads_bank0::gpio::W,
+    ) {
+        // SAFETY: we hold this pad's `PioPin`, so no other code is driving its pad registers.
+        unsafe { (*rp2040_pac::PADS_BANK0::ptr()).gpio[self.id as usize].modify(|_, w| f(w)) };
+    }
+}
+
+impl<P: Instance> Drop for PioPin<P> {
+    fn drop(&mut self) {
+        let previous =
+            PIN_REFCOUNTS[P::id() as usize][self.id as usize].fetch_sub(1, Ordering::SeqCst);
+        if previous == 1 {
+            // SAFETY: we were the last `PioPin` on this block referencing this GPIO, so it's
+            // safe to hand the pad back to `FunctionNull`.
+            unsafe {
+                (*rp2040_pac::IO_BANK0::ptr()).gpio[self.id as usize]
+                    .gpio_ctrl
+                    .write(|w| w.funcsel().bits(FUNCSEL_NULL));
+            }
+        }
+    }
+}
+
+/// Pad output drive strength, matching the `PADS_BANK0` `DRIVE` field encoding.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DriveStrength {
+    /// 2 mA.
+    TwoMilliAmps,
+    /// 4 mA.
+    FourMilliAmps,
+    /// 8 mA.
+    EightMilliAmps,
+    /// 12 mA.
+    TwelveMilliAmps,
+}
+
+impl DriveStrength {
+    fn bits(self) -> u8 {
+        match self {
+            DriveStrength::TwoMilliAmps => 0,
+            DriveStrength::FourMilliAmps => 1,
+            DriveStrength::EightMilliAmps => 2,
+            DriveStrength::TwelveMilliAmps => 3,
+        }
+    }
 }
 
 /// Builder to deploy a fully configured PIO program on one of the state
 /// machines.
 #[derive(Debug)]
-pub struct PIOBuilder<'a> {
+pub struct PIOBuilder<'a, P: Instance> {
     instructions: &'a [u16],
     instruction_offset: Option<u8>,
     // wrap program from top to bottom
@@ -198,7 +794,7 @@ pub struct PIOBuilder<'a> {
     // sideset sets pindirs
     side_pindir: bool,
     // gpio pin used by `jmp pin` instr
-    jmp_pin: u8,
+    jmp_pin: Option<PioPin<P>>,
     // continuously assert the most recent OUT/SET to the pins.
     out_sticky: bool,
     // use a bit of OUT data as an auxilary write enable.
@@ -212,12 +808,12 @@ pub struct PIOBuilder<'a> {
     status_sel: bool,
     // base = starting pin
     // count = number of pins
-    in_base: u8,
-    out_base: u8,
+    in_base: Option<PioPin<P>>,
+    out_base: Option<PioPin<P>>,
     out_count: u8,
-    set_base: u8,
+    set_base: Option<PioPin<P>>,
     set_count: u8,
-    sideset_base: u8,
+    sideset_base: Option<PioPin<P>>,
     sideset_count: u8,
     // rx fifo steals tx fifo storage to be twice as deep
     fjoin_rx: bool,
@@ -238,7 +834,7 @@ pub struct PIOBuilder<'a> {
     clock_divisor: f32,
 }
 
-impl<'a> Default for PIOBuilder<'a> {
+impl<'a, P: Instance> Default for PIOBuilder<'a, P> {
     fn default() -> Self {
         PIOBuilder {
             instructions: &[],
@@ -247,17 +843,17 @@ impl<'a> Default for PIOBuilder<'a> {
             wrap_bottom: 31,
             side_en: false,
             side_pindir: false,
-            jmp_pin: 0,
+            jmp_pin: None,
             out_sticky: false,
             inline_out_en: false,
             out_en_sel: 0,
             status_sel: false,
-            in_base: 0,
-            out_base: 0,
+            in_base: None,
+            out_base: None,
             out_count: 32,
-            set_base: 0,
+            set_base: None,
             set_count: 0,
-            sideset_base: 0,
+            sideset_base: None,
             sideset_count: 0,
             fjoin_rx: false,
             fjoin_tx: false,
@@ -290,14 +886,18 @@ pub enum BuildError {
     NoSpace,
 }
 
-impl<'a> PIOBuilder<'a> {
+impl<'a, P: Instance> PIOBuilder<'a, P> {
     /// Set config settings based on information from the given `pio::Program`.
     /// Additional configuration may be needed in addition to this.
-    pub fn with_program<P>(&mut self, p: &'a pio::Program<P>) -> &mut Self {
+    pub fn with_program<T>(&mut self, p: &'a pio::Program<T>) -> &mut Self {
         self.instructions(p.code());
 
         self.wrap(p.wrap().0, p.wrap().1);
 
+        // Honor a fixed load offset declared by the program itself (via `.origin`), so it ends
+        // up somewhere its internal `jmp` targets (relocated at `build` time) are actually valid.
+        self.instruction_offset = p.origin();
+
         self.side_en = p.side_set().optional();
         self.side_pindir = p.side_set().pindirs();
 
@@ -346,12 +946,70 @@ impl<'a> PIOBuilder<'a> {
         self
     }
 
-    /// Build the config and deploy it to a StateMachine.
-    pub fn build<P: Instance>(self, pio: &PIO<P>, sm: &StateMachine<P>) -> Result<(), BuildError> {
-        let offset = match pio.add_program(self.instructions, self.instruction_offset) {
-            Some(o) => o,
-            None => return Err(BuildError::NoSpace),
-        };
+    /// Set the pin used by a `jmp pin` instruction.
+    pub fn jmp_pin(&mut self, pin: PioPin<P>) -> &mut Self {
+        self.jmp_pin = Some(pin);
+        self
+    }
+
+    /// Set the pins used by `in` instructions. `base` is the first pin; the remaining pins in
+    /// the `in` shift group follow it in increasing GPIO number order.
+    pub fn in_pin_base(&mut self, base: PioPin<P>) -> &mut Self {
+        self.in_base = Some(base);
+        self
+    }
+
+    /// Set the pins written by `out` instructions. `count` is the number of consecutive pins
+    /// starting at `base`.
+    pub fn out_pins(&mut self, base: PioPin<P>, count: u8) -> &mut Self {
+        self.out_count = count;
+        self.out_base = Some(base);
+        self
+    }
+
+    /// Set the pins written by `set` instructions. `count` is the number of consecutive pins
+    /// starting at `base`.
+    pub fn set_pins(&mut self, base: PioPin<P>, count: u8) -> &mut Self {
+        self.set_count = count;
+        self.set_base = Some(base);
+        self
+    }
+
+    /// Set the first pin written by the program's side-set. The number of pins is taken from the
+    /// program itself via [`PIOBuilder::with_program`].
+    pub fn side_set_pin_base(&mut self, base: PioPin<P>) -> &mut Self {
+        self.sideset_base = Some(base);
+        self
+    }
+
+    /// Build the config and deploy it to a StateMachine, returning the installed program's
+    /// handle so it can later be reclaimed via [`PIO::uninstall`].
+    pub fn build(
+        self,
+        pio: &PIO<P>,
+        sm: &StateMachine<P>,
+    ) -> Result<InstalledProgram<P>, BuildError> {
+        // Relocate the program to wherever there's free instruction memory (or the fixed
+        // offset it declared via `.origin`) before writing it, so any internal `jmp`s stay
+        // correct no matter where it actually ends up loaded.
+        let relocate_offset = pio
+            .find_offset_for_instructions(self.instructions, self.instruction_offset)
+            .ok_or(BuildError::NoSpace)? as u8;
+        let relocated = RelocatedProgram::from_parts(
+            self.instructions,
+            (self.wrap_top, self.wrap_bottom),
+            self.instruction_offset,
+            relocate_offset,
+        );
+        let program = pio.add_program(&relocated).ok_or(BuildError::NoSpace)?;
+        let offset = program.offset();
+        let (wrap_top, wrap_bottom) = relocated.wrap();
+
+        let jmp_pin = self.jmp_pin.as_ref().map_or(0, PioPin::id);
+        let in_base = self.in_base.as_ref().map_or(0, PioPin::id);
+        let out_base = self.out_base.as_ref().map_or(0, PioPin::id);
+        let set_base = self.set_base.as_ref().map_or(0, PioPin::id);
+        let sideset_base = self.sideset_base.as_ref().map_or(0, PioPin::id);
 
         // ### STOP SM ####
         sm.set_enabled(false);
@@ -359,15 +1017,15 @@ impl<'a> PIOBuilder<'a> {
         // ### CONFIGURE SM ###
         sm.sm().sm_execctrl.write(|w| {
             unsafe {
-                w.wrap_top().bits(offset as u8 + self.wrap_top);
-                w.wrap_bottom().bits(offset as u8 + self.wrap_bottom);
+                w.wrap_top().bits(wrap_top);
+                w.wrap_bottom().bits(wrap_bottom);
             }
 
             w.side_en().bit(self.side_en);
             w.side_pindir().bit(self.side_pindir);
 
             unsafe {
-                w.jmp_pin().bits(self.jmp_pin);
+                w.jmp_pin().bits(jmp_pin);
             }
 
             w.out_sticky().bit(self.out_sticky);
@@ -384,21 +1042,21 @@ impl<'a> PIOBuilder<'a> {
 
         sm.sm().sm_pinctrl.write(|w| {
             unsafe {
-                w.in_base().bits(self.in_base);
+                w.in_base().bits(in_base);
             }
 
             unsafe {
-                w.out_base().bits(self.out_base);
+                w.out_base().bits(out_base);
                 w.out_count().bits(self.out_count);
             }
 
             unsafe {
-                w.set_base().bits(self.set_base);
+                w.set_base().bits(set_base);
                 w.set_count().bits(self.set_count);
             }
 
             unsafe {
-                w.sideset_base().bits(self.sideset_base);
+                w.sideset_base().bits(sideset_base);
                 w.sideset_count().bits(self.sideset_count);
             }
 
@@ -440,6 +1098,227 @@ impl<'a> PIOBuilder<'a> {
         // ### ENABLE SM ###
         sm.set_enabled(true);
 
-        Ok(())
+        // Hang on to the pins we just configured (dropping whatever this state machine owned
+        // before), so their pads stay routed to this PIO block for as long as it's in use.
+        *sm.pins.borrow_mut() = [
+            self.in_base,
+            self.out_base,
+            self.set_base,
+            self.sideset_base,
+            self.jmp_pin,
+        ];
+
+        Ok(program)
+    }
+}
+
+/// Build and immediately execute raw PIO instructions on a stopped state machine.
+///
+/// Manual control like this is useful for drivers that need to seed `X`/`Y` or jump to a
+/// particular wrap target before (re-)enabling a state machine for each transaction, without
+/// hand-assembling opcodes at every call site. Each function here encodes one instruction and
+/// feeds it through [`StateMachine::set_instruction`]; the state machine should be stopped first
+/// (`set_enabled(false)`), since an instruction executed this way still has its normal side
+/// effects on `X`/`Y`/the FIFOs/the program counter.
+pub mod instr {
+    use super::{Instance, StateMachine};
+
+    /// Destination of an `out` instruction.
+    ///
+    /// `out` and `mov` use different 3-bit destination encodings on real PIO hardware (e.g.
+    /// `out`'s `PINDIRS` and `mov`'s `EXEC` share code `100`), so each gets its own enum rather
+    /// than a shared one that would silently pun between them.
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub enum OutDestination {
+        /// The pins selected by `OUT_BASE`/`OUT_COUNT`.
+        Pins,
+        /// The `X` scratch register.
+        X,
+        /// The `Y` scratch register.
+        Y,
+        /// Discard the shifted-out bits.
+        Null,
+        /// The pin directions selected by `OUT_BASE`/`OUT_COUNT`.
+        PinDirs,
+        /// The program counter.
+        Pc,
+        /// The input shift register (also sets its shift count).
+        Isr,
+        /// Execute the shifted-out bits as an instruction.
+        Exec,
+    }
+
+    impl OutDestination {
+        fn bits(self) -> u8 {
+            match self {
+                OutDestination::Pins => 0b000,
+                OutDestination::X => 0b001,
+                OutDestination::Y => 0b010,
+                OutDestination::Null => 0b011,
+                OutDestination::PinDirs => 0b100,
+                OutDestination::Pc => 0b101,
+                OutDestination::Isr => 0b110,
+                OutDestination::Exec => 0b111,
+            }
+        }
+    }
+
+    /// Destination of a `mov` instruction. See [`OutDestination`] for why this isn't shared with
+    /// `out`.
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub enum MovDestination {
+        /// The pins selected by `OUT_BASE`/`OUT_COUNT`.
+        Pins,
+        /// The `X` scratch register.
+        X,
+        /// The `Y` scratch register.
+        Y,
+        /// Execute the moved value as an instruction.
+        Exec,
+        /// The program counter.
+        Pc,
+        /// The input shift register.
+        Isr,
+        /// The output shift register.
+        Osr,
+    }
+
+    impl MovDestination {
+        fn bits(self) -> u8 {
+            match self {
+                MovDestination::Pins => 0b000,
+                MovDestination::X => 0b001,
+                MovDestination::Y => 0b010,
+                MovDestination::Exec => 0b100,
+                MovDestination::Pc => 0b101,
+                MovDestination::Isr => 0b110,
+                MovDestination::Osr => 0b111,
+            }
+        }
+    }
+
+    /// Destination of a `set` instruction. `set`'s immediate is only 5 bits wide, so unlike
+    /// `out`/`mov` it can only reach the pins, `X`/`Y`, or the pin directions.
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub enum SetDestination {
+        /// The pins selected by `SET_BASE`/`SET_COUNT`.
+        Pins,
+        /// The `X` scratch register.
+        X,
+        /// The `Y` scratch register.
+        Y,
+        /// The pin directions selected by `SET_BASE`/`SET_COUNT`.
+        PinDirs,
+    }
+
+    impl SetDestination {
+        fn bits(self) -> u8 {
+            match self {
+                SetDestination::Pins => 0b000,
+                SetDestination::X => 0b001,
+                SetDestination::Y => 0b010,
+                SetDestination::PinDirs => 0b100,
+            }
+        }
+    }
+
+    /// Source of a `mov`/`in` instruction.
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub enum Source {
+        /// The pins selected by `IN_BASE`.
+        Pins,
+        /// The `X` scratch register.
+        X,
+        /// The `Y` scratch register.
+        Y,
+        /// All zeroes.
+        Null,
+        /// TX/RX FIFO level compared against `STATUS_N`.
+        Status,
+        /// The input shift register.
+        Isr,
+        /// The output shift register.
+        Osr,
+    }
+
+    impl Source {
+        fn bits(self) -> u8 {
+            match self {
+                Source::Pins => 0b000,
+                Source::X => 0b001,
+                Source::Y => 0b010,
+                Source::Null => 0b011,
+                Source::Status => 0b101,
+                Source::Isr => 0b110,
+                Source::Osr => 0b111,
+            }
+        }
+    }
+
+    const OPCODE_JMP: u16 = 0b000 << 13;
+    const OPCODE_OUT: u16 = 0b011 << 13;
+    const OPCODE_PUSH_PULL: u16 = 0b100 << 13;
+    const OPCODE_MOV: u16 = 0b101 << 13;
+    const OPCODE_SET: u16 = 0b111 << 13;
+
+    const PUSH_PULL_IS_PULL: u16 = 1 << 7;
+    const PUSH_PULL_BLOCK: u16 = 1 << 5;
+
+    /// Execute an unconditional jump to instruction memory offset `addr`.
+    pub fn exec_jmp<P: Instance>(sm: &StateMachine<P>, addr: u8) {
+        sm.set_instruction(OPCODE_JMP | (addr as u16 & 0x1f));
+    }
+
+    /// Execute `out <dest>, <bit_count>`, shifting `bit_count` bits out of the OSR.
+    pub fn out<P: Instance>(sm: &StateMachine<P>, dest: OutDestination, bit_count: u8) {
+        sm.set_instruction(OPCODE_OUT | ((dest.bits() as u16) << 5) | (bit_count as u16 & 0x1f));
+    }
+
+    /// Execute `mov <dest>, <src>`.
+    pub fn mov<P: Instance>(sm: &StateMachine<P>, dest: MovDestination, src: Source) {
+        sm.set_instruction(OPCODE_MOV | ((dest.bits() as u16) << 5) | src.bits() as u16);
+    }
+
+    /// Execute a blocking `push`, stalling until the RX FIFO has room for the ISR's contents.
+    pub fn push<P: Instance>(sm: &StateMachine<P>) {
+        sm.set_instruction(OPCODE_PUSH_PULL | PUSH_PULL_BLOCK);
+    }
+
+    /// Execute a blocking `pull`, stalling until the TX FIFO has a word to load into the OSR.
+    pub fn pull<P: Instance>(sm: &StateMachine<P>) {
+        sm.set_instruction(OPCODE_PUSH_PULL | PUSH_PULL_IS_PULL | PUSH_PULL_BLOCK);
+    }
+
+    /// Execute `set <dest>, <data>` (`data` is 0..=31, matching the instruction's 5-bit
+    /// immediate).
+    pub fn set<P: Instance>(sm: &StateMachine<P>, dest: SetDestination, data: u8) {
+        sm.set_instruction(OPCODE_SET | ((dest.bits() as u16) << 5) | (data as u16 & 0x1f));
+    }
+
+    /// Execute `set pins, <value>`.
+    pub fn set_pins<P: Instance>(sm: &StateMachine<P>, value: u8) {
+        set(sm, SetDestination::Pins, value);
+    }
+
+    /// Execute `set pindirs, <value>`.
+    pub fn set_pindir<P: Instance>(sm: &StateMachine<P>, value: u8) {
+        set(sm, SetDestination::PinDirs, value);
+    }
+
+    /// Set the `X` scratch register to an arbitrary 32-bit `value`.
+    ///
+    /// `set` can only load a 5-bit immediate, so this pushes `value` through the TX FIFO and
+    /// executes `pull` followed by `mov x, osr` to load it in full.
+    pub fn set_x<P: Instance>(sm: &StateMachine<P>, value: u32) {
+        sm.push(value);
+        pull(sm);
+        mov(sm, MovDestination::X, Source::Osr);
+    }
+
+    /// Set the `Y` scratch register to an arbitrary 32-bit `value`, the same way as [`set_x`].
+    pub fn set_y<P: Instance>(sm: &StateMachine<P>, value: u32) {
+        sm.push(value);
+        pull(sm);
+        mov(sm, MovDestination::Y, Source::Osr);
     }
 }